@@ -0,0 +1,189 @@
+use std::io::Write;
+
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+
+use crate::response::{Body, Response};
+
+/// Bodies smaller than this aren't worth the CPU cost of compressing.
+const MIN_COMPRESSIBLE_BYTES: usize = 1024;
+
+/// Content-encodings this server knows how to produce, in preference order.
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+}
+
+/// Incremental gzip/deflate encoder for [`Body::Chunked`] responses, so
+/// streamed files get compressed without ever buffering the whole file in
+/// memory. Each [`push`](StreamEncoder::push) call feeds it one window read
+/// from disk and returns whatever compressed bytes are ready to send; a
+/// [`Write::flush`] after every window forces those bytes out immediately
+/// (a small compression-ratio cost versus buffering the whole stream, paid
+/// for keeping memory use bounded) instead of waiting for the encoder to
+/// fill its own internal buffer.
+pub enum StreamEncoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(DeflateEncoder<Vec<u8>>),
+}
+
+impl StreamEncoder {
+    fn for_encoding(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Gzip => StreamEncoder::Gzip(GzEncoder::new(Vec::new(), Compression::default())),
+            Encoding::Deflate => StreamEncoder::Deflate(DeflateEncoder::new(Vec::new(), Compression::default())),
+        }
+    }
+
+    /// Feeds one window of input through the encoder and drains whatever
+    /// compressed output is ready.
+    pub fn push(&mut self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            StreamEncoder::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+            StreamEncoder::Deflate(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+        }
+    }
+
+    /// Finalizes the stream, returning the trailing bytes (e.g. the gzip
+    /// CRC/size trailer) that have to follow the last pushed chunk.
+    pub fn finish(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            StreamEncoder::Gzip(encoder) => encoder.finish(),
+            StreamEncoder::Deflate(encoder) => encoder.finish(),
+        }
+    }
+}
+
+/// Picks the best encoding this server supports out of an `Accept-Encoding`
+/// header value, preferring gzip over deflate. Returns `None` if the client
+/// didn't ask for (or we don't support) anything.
+fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+    let tokens: Vec<&str> = accept_encoding
+        .split(',')
+        .map(|token| token.split(';').next().unwrap_or("").trim())
+        .collect();
+
+    if tokens.iter().any(|t| t.eq_ignore_ascii_case("gzip")) {
+        Some(Encoding::Gzip)
+    } else if tokens.iter().any(|t| t.eq_ignore_ascii_case("deflate")) {
+        Some(Encoding::Deflate)
+    } else {
+        None
+    }
+}
+
+/// Whether a MIME type is worth compressing.
+fn is_compressible(content_type: &str) -> bool {
+    let base = content_type.split(';').next().unwrap_or("").trim();
+    base.starts_with("text/") || base == "application/javascript" || base == "application/json"
+}
+
+/// Compresses `response` in place if the client's `Accept-Encoding` allows
+/// it, the body is a compressible MIME type, and it's large enough to be
+/// worth it. Applied as a post-processing step over whatever
+/// `route_request` produced, so it covers every route uniformly.
+pub fn apply(response: Response, accept_encoding: Option<&str>) -> Response {
+    let Some(accept_encoding) = accept_encoding else {
+        return response;
+    };
+    let Some(encoding) = negotiate(accept_encoding) else {
+        return response;
+    };
+
+    let content_type = response
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Content-Type"))
+        .map(|(_, value)| value.clone());
+    let Some(content_type) = content_type else {
+        return response;
+    };
+    if !is_compressible(&content_type) {
+        return response;
+    }
+
+    match response.body {
+        Body::Bytes(body) => {
+            if body.len() < MIN_COMPRESSIBLE_BYTES {
+                return Response {
+                    body: Body::Bytes(body),
+                    ..response
+                };
+            }
+
+            let compressed = match encoding {
+                Encoding::Gzip => {
+                    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(&body).and_then(|_| encoder.finish())
+                }
+                Encoding::Deflate => {
+                    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(&body).and_then(|_| encoder.finish())
+                }
+            };
+            let compressed = match compressed {
+                Ok(compressed) => compressed,
+                Err(_) => {
+                    return Response {
+                        body: Body::Bytes(body),
+                        ..response
+                    }
+                }
+            };
+
+            let mut headers: Vec<(String, String)> = response
+                .headers
+                .into_iter()
+                .filter(|(name, _)| !name.eq_ignore_ascii_case("Content-Length"))
+                .collect();
+            headers.push(("Content-Length".to_string(), compressed.len().to_string()));
+            headers.push(("Content-Encoding".to_string(), encoding.as_str().to_string()));
+            headers.push(("Vary".to_string(), "Accept-Encoding".to_string()));
+
+            Response {
+                status: response.status,
+                headers,
+                body: Body::Bytes(compressed),
+            }
+        }
+        Body::Chunked(file) => {
+            // Large files are already well above MIN_COMPRESSIBLE_BYTES (they're
+            // only chunked once they pass STREAM_THRESHOLD_BYTES), so there's no
+            // size guard here: compress every eligible chunked file, piping each
+            // window read from disk through a StreamEncoder rather than
+            // buffering the whole file to compress it in one shot.
+            let encoding_name = encoding.as_str().to_string();
+            let mut headers: Vec<(String, String)> = response
+                .headers
+                .into_iter()
+                .filter(|(name, _)| !name.eq_ignore_ascii_case("Content-Length"))
+                .collect();
+            headers.push(("Content-Encoding".to_string(), encoding_name));
+            headers.push(("Vary".to_string(), "Accept-Encoding".to_string()));
+
+            Response {
+                status: response.status,
+                headers,
+                body: Body::ChunkedCompressed(file, StreamEncoder::for_encoding(encoding)),
+            }
+        }
+        body @ Body::ChunkedCompressed(..) => Response { body, ..response },
+    }
+}
@@ -1,9 +1,38 @@
 use tokio::net::TcpListener;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::fs;
+use tokio::net::TcpStream;
+use tokio::io::AsyncReadExt;
+use tokio::time::{timeout, Duration};
 use std::collections::HashMap;
 use tracing::{info, warn, error}; // Structured logging
 
+mod compression;
+mod negotiation;
+mod params;
+mod response;
+mod static_files;
+use response::Response;
+
+/// Maximum number of header bytes we're willing to buffer before giving up
+/// on a request. Keeps a slow or malicious client from growing the buffer
+/// without bound while we wait for `\r\n\r\n`.
+const MAX_HEADER_BYTES: usize = 8 * 1024;
+
+/// Size of each individual `read` performed while accumulating a request.
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Maximum body size we're willing to buffer for a single request. Caps
+/// memory use for a single connection task regardless of what
+/// `Content-Length` a client claims.
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// Methods that never carry a request body, so a missing `Content-Length`
+/// is expected rather than an error.
+const BODYLESS_METHODS: [&str; 4] = ["GET", "HEAD", "OPTIONS", "DELETE"];
+
+/// How long a keep-alive connection may sit idle before we give up and
+/// close it.
+const KEEP_ALIVE_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
     // Initialize structured logging
@@ -19,40 +48,190 @@ async fn main() -> std::io::Result<()> {
         let (mut socket, addr) = listener.accept().await?;
         info!("New connection established from: {}", addr);
 
-        // Spawn a new task to handle the connection asynchronously
+        // Spawn a new task to handle the connection asynchronously. The
+        // connection stays open across requests until the client (or the
+        // idle timeout) asks us to close it.
         tokio::spawn(async move {
-            let mut buffer = vec![0; 4096]; // Allocate a buffer to read incoming data
-            let bytes_read = match socket.read(&mut buffer).await {
-                Ok(0) => {
-                    warn!("Connection closed by client");
-                    return; // Connection was closed
-                }
-                Ok(n) => n, // Successfully read n bytes
-                Err(e) => {
-                    error!("Failed to read request: {}", e);
-                    return; // Log and exit on error
-                }
-            };
+            let mut carry_over = Vec::new();
+            loop {
+                let raw = match timeout(KEEP_ALIVE_IDLE_TIMEOUT, read_request(&mut socket, carry_over)).await {
+                    Ok(Ok(Some((raw, next_carry_over)))) => {
+                        carry_over = next_carry_over;
+                        raw
+                    }
+                    Ok(Ok(None)) => {
+                        info!("Connection closed by client");
+                        return;
+                    }
+                    Ok(Err(RequestReadError::HeadersTooLarge)) => {
+                        warn!("Rejecting request: headers exceeded {} bytes", MAX_HEADER_BYTES);
+                        let _ = Response::text("400 BAD REQUEST", "Headers Too Large").write(&mut socket).await;
+                        return;
+                    }
+                    Ok(Err(RequestReadError::MissingContentLength)) => {
+                        warn!("Rejecting request with an ambiguous or missing Content-Length");
+                        let _ = Response::text("411 LENGTH REQUIRED", "Content-Length Required").write(&mut socket).await;
+                        return;
+                    }
+                    Ok(Err(RequestReadError::BodyTooLarge)) => {
+                        warn!("Rejecting request: Content-Length exceeded {} bytes", MAX_BODY_BYTES);
+                        let _ = Response::text("413 PAYLOAD TOO LARGE", "Payload Too Large").write(&mut socket).await;
+                        return;
+                    }
+                    Ok(Err(RequestReadError::Io(e))) => {
+                        error!("Failed to read request: {}", e);
+                        return;
+                    }
+                    Err(_) => {
+                        info!("Closing idle keep-alive connection from: {}", addr);
+                        return;
+                    }
+                };
 
-            // Convert the buffer into a request string
-            let request = String::from_utf8_lossy(&buffer[..bytes_read]);
+                // Convert the buffer into a request string
+                let request = String::from_utf8_lossy(&raw).into_owned();
 
-            // Parse the HTTP request (method, path, headers, and body)
-            let (method, path, headers, body) = parse_request_with_body(&request);
+                // Parse the HTTP request (method, path, version, headers, and body)
+                let (method, path, version, headers, body) = parse_request_with_body(&request);
+                let (path, query_params) = params::parse_query_string(path);
+                let accept_encoding = headers.get("Accept-Encoding").cloned();
+                let accept = headers.get("Accept").cloned();
+                let keep_alive = should_keep_alive(version, headers.get("Connection").map(String::as_str));
 
-            // Route the request and generate a response
-            let response = route_request(method, path, headers, body).await;
+                // Route the request and generate a response
+                let response = route_request(method, path, version, headers, body, query_params, accept.as_deref()).await;
+                let mut response = compression::apply(response, accept_encoding.as_deref());
+                response.set_header("Connection", if keep_alive { "keep-alive" } else { "close" });
 
-            // Send the response back to the client
-            if let Err(e) = socket.write_all(response.as_bytes()).await {
-                error!("Failed to send response: {}", e);
-            } else {
+                // Send the response back to the client
+                if let Err(e) = response.write(&mut socket).await {
+                    error!("Failed to send response: {}", e);
+                    return;
+                }
                 info!("Response successfully sent to client");
+
+                if !keep_alive {
+                    return;
+                }
             }
         });
     }
 }
 
+/// Errors that can occur while assembling a full request off the wire.
+enum RequestReadError {
+    /// The header section grew past `MAX_HEADER_BYTES` without `\r\n\r\n`.
+    HeadersTooLarge,
+    /// A request carries (or may carry) a body but no `Content-Length`
+    /// header, so we have no way to know where the body ends.
+    MissingContentLength,
+    /// `Content-Length` claims a body larger than `MAX_BODY_BYTES`.
+    BodyTooLarge,
+    Io(std::io::Error),
+}
+
+/// Reads one full HTTP request (headers plus body) from `socket`, starting
+/// from whatever bytes of a pipelined next request were already buffered
+/// (`carry_over`).
+///
+/// Bytes are accumulated into a growing buffer until the `\r\n\r\n` header
+/// terminator is found. The `Content-Length` header (if any) is then read
+/// out of that prefix and used to keep reading until the full body has
+/// arrived, starting from whatever body bytes already landed in the header
+/// buffer. Returns `Ok(None)` if the connection closed before any bytes were
+/// read at all.
+///
+/// Returns the request bytes together with any bytes read past the end of
+/// this request's body — the start of a pipelined next request — so the
+/// caller can feed them back in as `carry_over` on the next call.
+async fn read_request(
+    socket: &mut TcpStream,
+    carry_over: Vec<u8>,
+) -> Result<Option<(Vec<u8>, Vec<u8>)>, RequestReadError> {
+    let mut buffer = carry_over;
+    let mut chunk = [0u8; READ_CHUNK_SIZE];
+
+    let header_end = loop {
+        if let Some(end) = find_header_end(&buffer) {
+            break end;
+        }
+        if buffer.len() > MAX_HEADER_BYTES {
+            return Err(RequestReadError::HeadersTooLarge);
+        }
+        let n = socket.read(&mut chunk).await.map_err(RequestReadError::Io)?;
+        if n == 0 {
+            return if buffer.is_empty() {
+                Ok(None)
+            } else {
+                Err(RequestReadError::Io(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "connection closed mid-headers",
+                )))
+            };
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    };
+
+    let header_text = String::from_utf8_lossy(&buffer[..header_end]);
+    let content_length = parse_content_length(&header_text);
+    let method = header_text.split_whitespace().next().unwrap_or("");
+
+    let body_len = match content_length {
+        Some(len) => {
+            if len > MAX_BODY_BYTES {
+                return Err(RequestReadError::BodyTooLarge);
+            }
+            len
+        }
+        None => {
+            // No Content-Length: that's only unambiguous for methods that
+            // never carry a body, where the body is always zero-length and
+            // anything already buffered past the headers is unambiguously
+            // the start of a pipelined next request. For any other method
+            // we can't tell where this request ends, so reject it outright
+            // rather than silently reinterpreting body bytes as framing.
+            if !BODYLESS_METHODS.contains(&method) {
+                return Err(RequestReadError::MissingContentLength);
+            }
+            0
+        }
+    };
+
+    let body_end = header_end
+        .checked_add(body_len)
+        .ok_or(RequestReadError::BodyTooLarge)?;
+    while buffer.len() < body_end {
+        let n = socket.read(&mut chunk).await.map_err(RequestReadError::Io)?;
+        if n == 0 {
+            return Err(RequestReadError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "connection closed mid-body",
+            )));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    let carry_over = buffer.split_off(body_end);
+    Ok(Some((buffer, carry_over)))
+}
+
+/// Finds the index right after the `\r\n\r\n` header terminator, if present.
+fn find_header_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4)
+}
+
+/// Looks up `Content-Length` in a block of raw header text, case-insensitively.
+fn parse_content_length(header_text: &str) -> Option<usize> {
+    header_text.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        if key.trim().eq_ignore_ascii_case("content-length") {
+            value.trim().parse::<usize>().ok()
+        } else {
+            None
+        }
+    })
+}
+
 /// Parses the HTTP request and extracts the method, path, headers, and body.
 ///
 /// # Arguments
@@ -62,124 +241,127 @@ async fn main() -> std::io::Result<()> {
 /// A tuple containing:
 /// - `method`: HTTP method (e.g., "GET", "POST")
 /// - `path`: Request path (e.g., "/static/index.html")
+/// - `version`: HTTP version token (e.g., "HTTP/1.1")
 /// - `headers`: Parsed headers as a HashMap
 /// - `body`: The request body (if any)
-fn parse_request_with_body(request: &str) -> (&str, &str, HashMap<String, String>, String) {
-    let mut lines = request.lines(); // Split the request into lines
+fn parse_request_with_body(request: &str) -> (&str, &str, &str, HashMap<String, String>, &str) {
+    let header_end = request.find("\r\n\r\n").map(|i| i + 4).unwrap_or(request.len());
+    let body = &request[header_end..];
+
+    let mut lines = request[..header_end].lines();
     let first_line = lines.next().unwrap_or_default(); // First line contains method, path, and protocol
     let mut parts = first_line.split_whitespace();
     let method = parts.next().unwrap_or(""); // Extract method (e.g., "GET")
     let path = parts.next().unwrap_or(""); // Extract path (e.g., "/static/file.txt")
+    let version = parts.next().unwrap_or("HTTP/1.0"); // Extract version (e.g., "HTTP/1.1")
 
     // Parse headers into a HashMap
     let mut headers = HashMap::new();
-    let mut body = String::new(); // Initialize an empty String for the body
-
-    for line in &mut lines {
-        if line.is_empty() {
-            // Stop parsing headers when we encounter an empty line
-            body = lines.collect::<Vec<&str>>().join("\n"); // Collect the remaining lines as the body
-            break;
-        }
+    for line in lines {
         if let Some((key, value)) = line.split_once(": ") {
             headers.insert(key.to_string(), value.to_string()); // Insert header key-value pairs
         }
     }
 
-    (method, path, headers, body)
+    (method, path, version, headers, body)
+}
+
+/// Decides whether the connection should stay open for another request,
+/// per the `Connection` header and the request's HTTP version: `close`
+/// always wins, `keep-alive` always keeps the socket open, and otherwise
+/// HTTP/1.1 defaults to keep-alive while HTTP/1.0 defaults to closing.
+fn should_keep_alive(version: &str, connection_header: Option<&str>) -> bool {
+    match connection_header.map(|v| v.to_ascii_lowercase()) {
+        Some(value) if value == "close" => false,
+        Some(value) if value == "keep-alive" => true,
+        _ => version == "HTTP/1.1",
+    }
 }
 
 /// Routes the HTTP request based on the method and path.
 ///
 /// # Arguments
 /// * `method` - HTTP method (e.g., "GET", "POST").
-/// * `path` - The request path.
+/// * `path` - The request path, with any query string already stripped.
+/// * `version` - HTTP version token (e.g., "HTTP/1.1").
 /// * `headers` - Request headers.
 /// * `body` - Request body.
+/// * `query_params` - Decoded query-string parameters.
+/// * `accept` - The request's `Accept` header, for content negotiation.
 ///
 /// # Returns
-/// A string containing the HTTP response.
+/// A [`Response`] to flush back to the client.
 async fn route_request(
     method: &str,
     path: &str,
+    version: &str,
     headers: HashMap<String, String>,
-    body: String,
-) -> String {
+    body: &str,
+    query_params: HashMap<String, Vec<String>>,
+    accept: Option<&str>,
+) -> Response {
     match method {
         "GET" => match path {
-            "/" => {
-                let body = "Welcome to the homepage!";
-                format!(
-                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
-                    body.len(),
-                    body
-                )
-            }
-            path if path.starts_with("/static/") => serve_file(&path[1..]).await,
-            _ => {
-                let body = "404 Not Found";
-                format!(
-                    "HTTP/1.1 404 NOT FOUND\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
-                    body.len(),
-                    body
-                )
-            }
+            "/" => negotiated_response(accept, "Welcome to the homepage!"),
+            path if path.starts_with("/static/") => static_files::serve(path, version).await,
+            _ => Response::text("404 NOT FOUND", "404 Not Found"),
         },
-        "POST" => handle_post(path, headers, body).await,
-        _ => {
-            let body = "405 Method Not Allowed";
-            format!(
-                "HTTP/1.1 405 METHOD NOT ALLOWED\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
-                body.len(),
-                body
-            )
-        }
+        "POST" => handle_post(path, headers, body, query_params, accept).await,
+        _ => Response::text("405 METHOD NOT ALLOWED", "405 Method Not Allowed"),
     }
 }
 
-/// Serves a file, dynamically setting the Content-Type header based on file extension.
-///
-/// # Arguments
-/// * `filepath` - The file path (relative to the project root).
-///
-/// # Returns
-/// A string containing the HTTP response with the file's content or a 404 error.
-async fn serve_file(filepath: &str) -> String {
-    match fs::read_to_string(filepath).await {
-        Ok(content) => {
-            let mime_type = get_mime_type(filepath); // Determine MIME type based on file extension
-            format!(
-                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
-                mime_type,
-                content.len(),
-                content
-            )
-        }
-        Err(_) => {
-            let body = "404 File Not Found";
-            format!(
-                "HTTP/1.1 404 NOT FOUND\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
-                body.len(),
-                body
-            )
-        }
+/// Producible representations, in preference order, for handlers that
+/// support content negotiation via [`negotiation::negotiate`].
+const NEGOTIABLE_TYPES: [&str; 2] = ["text/plain", "application/json"];
+
+/// Renders `message` as whichever of [`NEGOTIABLE_TYPES`] best matches the
+/// request's `Accept` header, or a `406 Not Acceptable` if none match.
+fn negotiated_response(accept: Option<&str>, message: &str) -> Response {
+    match negotiation::negotiate(accept, &NEGOTIABLE_TYPES).as_deref() {
+        Some("application/json") => Response::bytes(
+            "200 OK",
+            "application/json",
+            format!("{{\"message\":\"{}\"}}", json_escape(message)).into_bytes(),
+        ),
+        Some(_) => Response::text("200 OK", message),
+        None => Response::text("406 NOT ACCEPTABLE", "Not Acceptable"),
     }
 }
 
+/// Escapes a string for embedding in a JSON string literal.
+fn json_escape(input: &str) -> String {
+    input
+        .chars()
+        .flat_map(|c| match c {
+            '"' => vec!['\\', '"'],
+            '\\' => vec!['\\', '\\'],
+            '\n' => vec!['\\', 'n'],
+            '\r' => vec!['\\', 'r'],
+            '\t' => vec!['\\', 't'],
+            c => vec![c],
+        })
+        .collect()
+}
+
 /// Handles POST requests, processing the body.
 ///
 /// # Arguments
 /// * `path` - The request path.
 /// * `headers` - Parsed headers.
 /// * `body` - The request body.
+/// * `query_params` - Decoded query-string parameters.
+/// * `accept` - The request's `Accept` header, for content negotiation.
 ///
 /// # Returns
-/// A string containing the HTTP response.
+/// A [`Response`] to flush back to the client.
 async fn handle_post(
     path: &str,
     headers: HashMap<String, String>,
-    body: String,
-) -> String {
+    body: &str,
+    query_params: HashMap<String, Vec<String>>,
+    accept: Option<&str>,
+) -> Response {
     match path {
         "/submit" => {
             info!("Processing POST request to /submit with body: {}", body);
@@ -188,57 +370,26 @@ async fn handle_post(
             if let Some(content_type) = headers.get("Content-Type") {
                 if content_type == "application/json" {
                     info!("Received JSON payload");
-                    return format!(
-                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
-                        body.len(),
-                        format!("Received JSON: {}", body)
-                    );
+                    return negotiated_response(accept, &format!("Received JSON: {}", body));
                 } else if content_type == "application/x-www-form-urlencoded" {
                     info!("Received form-encoded payload");
-                    return format!(
-                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
-                        body.len(),
-                        format!("Received form data: {}", body)
-                    );
+                    let mut form_params = params::parse_form_body(body);
+                    for (key, values) in query_params {
+                        form_params.entry(key).or_default().extend(values);
+                    }
+                    let mut pairs: Vec<String> = form_params
+                        .iter()
+                        .flat_map(|(key, values)| values.iter().map(move |value| format!("{key}={value}")))
+                        .collect();
+                    pairs.sort();
+                    return negotiated_response(accept, &format!("Received form data: {}", pairs.join("&")));
                 }
             }
 
             // Default fallback for unsupported Content-Type
             warn!("Unsupported Content-Type: {:?}", headers.get("Content-Type"));
-            let response_body = "Unsupported Content-Type";
-            format!(
-                "HTTP/1.1 415 UNSUPPORTED MEDIA TYPE\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
-                response_body.len(),
-                response_body
-            )
+            Response::text("415 UNSUPPORTED MEDIA TYPE", "Unsupported Content-Type")
         }
-        _ => {
-            let body = "404 Not Found";
-            format!(
-                "HTTP/1.1 404 NOT FOUND\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
-                body.len(),
-                body
-            )
-        }
-    }
-}
-
-/// Determines the MIME type based on the file extension.
-///
-/// # Arguments
-/// * `filepath` - The file path.
-///
-/// # Returns
-/// The MIME type as a string.
-fn get_mime_type(filepath: &str) -> &str {
-    match filepath.rsplit('.').next() {
-        Some("html") => "text/html",
-        Some("css") => "text/css",
-        Some("js") => "application/javascript",
-        Some("json") => "application/json",
-        Some("png") => "image/png",
-        Some("jpg") | Some("jpeg") => "image/jpeg",
-        Some("txt") => "text/plain",
-        _ => "application/octet-stream", // Default binary type
+        _ => Response::text("404 NOT FOUND", "404 Not Found"),
     }
 }
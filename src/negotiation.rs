@@ -0,0 +1,84 @@
+/// A single entry in a parsed `Accept` header: a `type/subtype` media range
+/// together with its `q` quality value.
+struct MediaRange {
+    media_type: String,
+    subtype: String,
+    quality: f32,
+}
+
+impl MediaRange {
+    /// Higher is more specific: an exact type/subtype beats `type/*`, which
+    /// beats `*/*`. Used as the tiebreaker when two ranges share a quality.
+    fn specificity(&self) -> u8 {
+        match (self.media_type.as_str(), self.subtype.as_str()) {
+            ("*", "*") => 0,
+            (_, "*") => 1,
+            _ => 2,
+        }
+    }
+
+    fn matches(&self, candidate_type: &str, candidate_subtype: &str) -> bool {
+        (self.media_type == "*" || self.media_type == candidate_type)
+            && (self.subtype == "*" || self.subtype == candidate_subtype)
+    }
+}
+
+/// Parses an `Accept` header into media ranges sorted by quality
+/// (descending), then specificity (descending).
+fn parse_accept(header: &str) -> Vec<MediaRange> {
+    let mut ranges: Vec<MediaRange> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut segments = part.trim().split(';');
+            let media = segments.next()?.trim();
+            let (media_type, subtype) = media.split_once('/')?;
+
+            let mut quality = 1.0f32;
+            for param in segments {
+                if let Some(value) = param.trim().strip_prefix("q=") {
+                    quality = value.trim().parse().unwrap_or(1.0);
+                }
+            }
+
+            Some(MediaRange {
+                media_type: media_type.trim().to_string(),
+                subtype: subtype.trim().to_string(),
+                quality,
+            })
+        })
+        .collect();
+
+    ranges.sort_by(|a, b| {
+        b.quality
+            .partial_cmp(&a.quality)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| b.specificity().cmp(&a.specificity()))
+    });
+    ranges
+}
+
+/// Picks the best MIME type a handler can produce for a request's `Accept`
+/// header. `offered` should be listed in the handler's own preference
+/// order, since it's used as the tiebreaker when several offered types
+/// match the same media range (e.g. `*/*` or `text/*`); between
+/// equally-ranked media ranges, their order in the header decides.
+/// Returns `None` (which callers should turn into a 406) if nothing
+/// in `offered` satisfies any accepted range.
+///
+/// A missing `Accept` header is treated as `*/*`, i.e. anything goes.
+pub fn negotiate(accept_header: Option<&str>, offered: &[&str]) -> Option<String> {
+    let ranges = parse_accept(accept_header.unwrap_or("*/*"));
+
+    for range in &ranges {
+        if range.quality <= 0.0 {
+            continue;
+        }
+        for candidate in offered {
+            let (candidate_type, candidate_subtype) = candidate.split_once('/').unwrap_or((*candidate, ""));
+            if range.matches(candidate_type, candidate_subtype) {
+                return Some((*candidate).to_string());
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+/// Splits a request path at `?` and percent-decodes the query string into a
+/// parameter map. Repeated keys accumulate rather than overwrite.
+///
+/// # Returns
+/// A tuple of the path with the query string removed, and the decoded
+/// parameters.
+pub fn parse_query_string(path: &str) -> (&str, HashMap<String, Vec<String>>) {
+    match path.split_once('?') {
+        Some((base, query)) => (base, parse_encoded_pairs(query)),
+        None => (path, HashMap::new()),
+    }
+}
+
+/// Percent-decodes an `application/x-www-form-urlencoded` body into a
+/// parameter map, using the same decoder as [`parse_query_string`].
+pub fn parse_form_body(body: &str) -> HashMap<String, Vec<String>> {
+    parse_encoded_pairs(body)
+}
+
+/// Parses a `&`-separated, `=`-joined list of percent-encoded pairs, e.g.
+/// `a=b&c=d%20e`, accumulating repeated keys into a `Vec`.
+fn parse_encoded_pairs(input: &str) -> HashMap<String, Vec<String>> {
+    let mut params: HashMap<String, Vec<String>> = HashMap::new();
+    for pair in input.split('&') {
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params.entry(decode(key)).or_default().push(decode(value));
+    }
+    params
+}
+
+/// Percent-decodes a single key or value, treating `+` as a space per
+/// `application/x-www-form-urlencoded` convention. Malformed `%XX` escapes
+/// are left as-is rather than rejected.
+fn decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b'%' => match input
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    out.push(byte);
+                    i += 3;
+                }
+                None => {
+                    out.push(bytes[i]);
+                    i += 1;
+                }
+            },
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,133 @@
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::compression::StreamEncoder;
+
+/// Size of each window read from disk while streaming a chunked response.
+const CHUNK_READ_SIZE: usize = 16 * 1024;
+
+/// Source of a response body: either fully buffered bytes sent with a
+/// `Content-Length`, a file streamed via HTTP/1.1 chunked transfer encoding
+/// so memory use stays bounded regardless of file size, or that same
+/// streamed file with its chunks piped through a [`StreamEncoder`] so
+/// compression covers large files without buffering them whole.
+pub enum Body {
+    Bytes(Vec<u8>),
+    Chunked(File),
+    ChunkedCompressed(File, StreamEncoder),
+}
+
+/// A writable HTTP response: status line, headers, and a body source.
+/// Callers build one of these and flush it directly to the socket with
+/// [`Response::write`] instead of assembling the whole response as a
+/// `String` up front.
+pub struct Response {
+    pub status: &'static str,
+    pub headers: Vec<(String, String)>,
+    pub body: Body,
+}
+
+impl Response {
+    /// Builds a plain text response fully buffered in memory.
+    pub fn text(status: &'static str, body: impl Into<String>) -> Self {
+        let body = body.into().into_bytes();
+        Response {
+            status,
+            headers: vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("Content-Length".to_string(), body.len().to_string()),
+            ],
+            body: Body::Bytes(body),
+        }
+    }
+
+    /// Builds a fully buffered response with an explicit content type.
+    pub fn bytes(status: &'static str, content_type: &str, body: Vec<u8>) -> Self {
+        Response {
+            status,
+            headers: vec![
+                ("Content-Type".to_string(), content_type.to_string()),
+                ("Content-Length".to_string(), body.len().to_string()),
+            ],
+            body: Body::Bytes(body),
+        }
+    }
+
+    /// Sets a header, replacing any existing value with the same name
+    /// (case-insensitively) rather than duplicating it.
+    pub fn set_header(&mut self, name: &str, value: impl Into<String>) {
+        if let Some(existing) = self.headers.iter_mut().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            existing.1 = value.into();
+        } else {
+            self.headers.push((name.to_string(), value.into()));
+        }
+    }
+
+    /// Builds a response that streams `file` to the client using chunked
+    /// transfer encoding rather than buffering it whole.
+    pub fn chunked_file(status: &'static str, content_type: &str, file: File) -> Self {
+        Response {
+            status,
+            headers: vec![
+                ("Content-Type".to_string(), content_type.to_string()),
+                ("Transfer-Encoding".to_string(), "chunked".to_string()),
+            ],
+            body: Body::Chunked(file),
+        }
+    }
+
+    /// Writes the status line, headers, and body to `socket`.
+    pub async fn write(self, socket: &mut TcpStream) -> std::io::Result<()> {
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status);
+        for (name, value) in &self.headers {
+            head.push_str(name);
+            head.push_str(": ");
+            head.push_str(value);
+            head.push_str("\r\n");
+        }
+        head.push_str("\r\n");
+        socket.write_all(head.as_bytes()).await?;
+
+        match self.body {
+            Body::Bytes(bytes) => socket.write_all(&bytes).await,
+            Body::Chunked(mut file) => {
+                let mut buf = vec![0u8; CHUNK_READ_SIZE];
+                loop {
+                    let n = file.read(&mut buf).await?;
+                    if n == 0 {
+                        break;
+                    }
+                    write_chunk(socket, &buf[..n]).await?;
+                }
+                socket.write_all(b"0\r\n\r\n").await
+            }
+            Body::ChunkedCompressed(mut file, mut encoder) => {
+                let mut buf = vec![0u8; CHUNK_READ_SIZE];
+                loop {
+                    let n = file.read(&mut buf).await?;
+                    if n == 0 {
+                        break;
+                    }
+                    let compressed = encoder.push(&buf[..n])?;
+                    if !compressed.is_empty() {
+                        write_chunk(socket, &compressed).await?;
+                    }
+                }
+                let tail = encoder.finish()?;
+                if !tail.is_empty() {
+                    write_chunk(socket, &tail).await?;
+                }
+                socket.write_all(b"0\r\n\r\n").await
+            }
+        }
+    }
+}
+
+/// Writes a single chunked-transfer-encoding frame: hex length, `\r\n`, the
+/// bytes, then the trailing `\r\n`.
+async fn write_chunk(socket: &mut TcpStream, data: &[u8]) -> std::io::Result<()> {
+    socket.write_all(format!("{:x}\r\n", data.len()).as_bytes()).await?;
+    socket.write_all(data).await?;
+    socket.write_all(b"\r\n").await
+}
@@ -0,0 +1,195 @@
+use std::path::{Component, Path, PathBuf};
+
+use tokio::fs;
+
+use crate::response::Response;
+
+/// Base directory that `/static/` requests are rooted at. Requests can never
+/// resolve to a path outside of this directory.
+const STATIC_ROOT: &str = "static";
+
+/// Files at or above this size are streamed with chunked transfer encoding
+/// instead of being buffered whole in memory.
+const STREAM_THRESHOLD_BYTES: u64 = 256 * 1024;
+
+/// Serves a request under `/static/`. Percent-decodes the path, strips any
+/// `.`/`..` traversal components before touching the filesystem, and
+/// canonicalizes the result to confirm it's still inside [`STATIC_ROOT`]
+/// (guarding against symlinks that point back out). Directories are served
+/// via their `index.html` if present, otherwise a generated listing.
+///
+/// # Arguments
+/// * `request_path` - The full request path, e.g. `/static/css/site.css`.
+/// * `version` - The request's HTTP version token, e.g. `HTTP/1.1`. Large
+///   files are only streamed with chunked transfer encoding for HTTP/1.1
+///   requests, since `Transfer-Encoding: chunked` is illegal over HTTP/1.0.
+pub async fn serve(request_path: &str, version: &str) -> Response {
+    let relative = request_path
+        .strip_prefix("/static/")
+        .or_else(|| request_path.strip_prefix("/static"))
+        .unwrap_or("");
+
+    let decoded = match percent_decode(relative) {
+        Some(decoded) => decoded,
+        None => return Response::text("400 BAD REQUEST", "Invalid percent-encoding"),
+    };
+
+    let resolved = match resolve_within_root(&decoded).await {
+        Some(resolved) => resolved,
+        None => return Response::text("403 FORBIDDEN", "Forbidden"),
+    };
+
+    match fs::metadata(&resolved).await {
+        Ok(metadata) if metadata.is_dir() => serve_directory(&resolved, version).await,
+        Ok(_) => serve_regular_file(&resolved, version).await,
+        Err(_) => Response::text("404 NOT FOUND", "404 File Not Found"),
+    }
+}
+
+/// Percent-decodes a path segment, rejecting malformed `%XX` escapes.
+fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = input
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))?;
+                out.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Resolves `relative` against [`STATIC_ROOT`], dropping any `.`, `..`, or
+/// absolute-path components so the result can never climb above the root.
+/// If the candidate already exists, it's canonicalized and double-checked
+/// against the canonical root to catch symlinks that escape it. Uses
+/// `tokio::fs::canonicalize` rather than `std::fs::canonicalize` so the
+/// blocking syscalls run off the async reactor thread, like every other
+/// filesystem access in this module.
+async fn resolve_within_root(relative: &str) -> Option<PathBuf> {
+    let mut safe_relative = PathBuf::new();
+    for component in Path::new(relative).components() {
+        if let Component::Normal(part) = component {
+            safe_relative.push(part);
+        }
+        // CurDir, ParentDir, RootDir, and Prefix components are dropped.
+    }
+
+    let candidate = Path::new(STATIC_ROOT).join(&safe_relative);
+
+    if let Ok(canonical_candidate) = fs::canonicalize(&candidate).await {
+        let canonical_root = fs::canonicalize(STATIC_ROOT).await.ok()?;
+        if !canonical_candidate.starts_with(&canonical_root) {
+            return None;
+        }
+    }
+
+    Some(candidate)
+}
+
+/// Serves `index.html` if present, otherwise a generated directory listing.
+async fn serve_directory(dir: &Path, version: &str) -> Response {
+    let index = dir.join("index.html");
+    if fs::metadata(&index).await.is_ok() {
+        return serve_regular_file(&index, version).await;
+    }
+
+    let mut read_dir = match fs::read_dir(dir).await {
+        Ok(read_dir) => read_dir,
+        Err(_) => return Response::text("404 NOT FOUND", "404 File Not Found"),
+    };
+
+    let mut names = Vec::new();
+    while let Ok(Some(entry)) = read_dir.next_entry().await {
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+
+    let mut listing = String::from("<html><body><ul>\n");
+    for name in &names {
+        let href = percent_encode_path_segment(name);
+        let text = html_escape(name);
+        listing.push_str(&format!("<li><a href=\"{href}\">{text}</a></li>\n"));
+    }
+    listing.push_str("</ul></body></html>\n");
+
+    Response::bytes("200 OK", "text/html", listing.into_bytes())
+}
+
+/// Escapes a string for safe inclusion as HTML text content.
+fn html_escape(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Percent-encodes a path segment for safe inclusion in an `href` attribute.
+fn percent_encode_path_segment(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+/// Serves a regular file as raw bytes, streaming it via chunked transfer
+/// encoding once it's at or above `STREAM_THRESHOLD_BYTES` — but only for
+/// HTTP/1.1 requests, since `Transfer-Encoding: chunked` is illegal over
+/// HTTP/1.0 (RFC 7230 §3.3.1). HTTP/1.0 clients always get the file fully
+/// buffered with a `Content-Length`, regardless of size.
+async fn serve_regular_file(path: &Path, version: &str) -> Response {
+    let mime_type = get_mime_type(path);
+
+    let metadata = match fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(_) => return Response::text("404 NOT FOUND", "404 File Not Found"),
+    };
+
+    if metadata.len() >= STREAM_THRESHOLD_BYTES && version == "HTTP/1.1" {
+        match fs::File::open(path).await {
+            Ok(file) => Response::chunked_file("200 OK", mime_type, file),
+            Err(_) => Response::text("404 NOT FOUND", "404 File Not Found"),
+        }
+    } else {
+        match fs::read(path).await {
+            Ok(content) => Response::bytes("200 OK", mime_type, content),
+            Err(_) => Response::text("404 NOT FOUND", "404 File Not Found"),
+        }
+    }
+}
+
+/// Determines the MIME type based on the file extension.
+fn get_mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") => "text/html",
+        Some("css") => "text/css",
+        Some("js") => "application/javascript",
+        Some("json") => "application/json",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream", // Default binary type
+    }
+}